@@ -0,0 +1,102 @@
+//! Attested-TLS certificate generation.
+//!
+//! Follows the remote-attestation-TLS pattern used elsewhere in the confidential-computing
+//! ecosystem: generate an ephemeral TLS key pair, set the attestation report's
+//! `report_data` to a hash of the public key, and carry the report (and ideally its VCEK
+//! chain) inside a custom extension of a self-signed certificate for that key pair. A
+//! relying party that terminates a normal TLS handshake can then pull the extension out
+//! of the peer certificate, verify the report with `snp_validate_report`, and check that
+//! the certificate's public key hashes to the report's `report_data` — binding the TLS
+//! channel to the attested guest without any extra protocol round-trips.
+
+use rcgen::{Certificate, CertificateParams, CustomExtension, KeyPair, PKCS_ECDSA_P256_SHA256};
+use sha2::{Digest, Sha512};
+use snafu::{ResultExt, Whatever};
+
+/// Non-standard OID under which the raw attestation report (and, if present, its VCEK
+/// chain) is carried in the certificate. Not IANA-registered; relying parties must be
+/// configured to look for it explicitly, same as the cert itself being self-signed.
+pub const SNP_REPORT_EXTENSION_OID: &[u64] = &[1, 3, 6, 1, 4, 1, 311, 1, 1, 1];
+
+/// `report_data` is exactly 64 bytes, which `SHA-512(public_key_der)` fits precisely.
+pub fn report_data_for_public_key(public_key_der: &[u8]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(public_key_der);
+    hasher.finalize().into()
+}
+
+/// An ephemeral TLS key pair together with the `report_data` the attestation report
+/// must be requested with to bind to it.
+pub struct AttestedKeyPair {
+    pub key_pair: KeyPair,
+    pub report_data: [u8; 64],
+}
+
+/// Generates a fresh ECDSA P-256 TLS key pair and the `report_data` that binds an
+/// attestation report to it. Request the report with this `report_data` before calling
+/// `generate_attested_cert`.
+pub fn generate_attested_key_pair() -> Result<AttestedKeyPair, Whatever> {
+    let key_pair = KeyPair::generate(&PKCS_ECDSA_P256_SHA256)
+        .whatever_context("failed to generate ephemeral TLS key pair")?;
+    let report_data = report_data_for_public_key(&key_pair.public_key_der());
+    Ok(AttestedKeyPair { key_pair, report_data })
+}
+
+/// Builds a self-signed X.509 certificate for `attested_key_pair` carrying `report_bytes`
+/// (and, if given, `vcek_chain_der`) inside `SNP_REPORT_EXTENSION_OID`. Returns the
+/// certificate and private key, both PEM-encoded.
+pub fn generate_attested_cert(
+    attested_key_pair: AttestedKeyPair,
+    report_bytes: &[u8],
+    vcek_chain_der: Option<&[u8]>,
+) -> Result<(String, String), Whatever> {
+    let mut extension_value = Vec::with_capacity(report_bytes.len() + vcek_chain_der.map_or(0, <[u8]>::len));
+    extension_value.extend_from_slice(report_bytes);
+    if let Some(vcek_chain_der) = vcek_chain_der {
+        extension_value.extend_from_slice(vcek_chain_der);
+    }
+
+    let mut params = CertificateParams::new(vec!["snp-guard-attested".to_string()]);
+    params.key_pair = Some(attested_key_pair.key_pair);
+    params
+        .custom_extensions
+        .push(CustomExtension::from_oid_content(SNP_REPORT_EXTENSION_OID, extension_value));
+
+    let cert = Certificate::from_params(params)
+        .whatever_context("failed to build self-signed attested TLS certificate")?;
+    let cert_pem = cert
+        .serialize_pem()
+        .whatever_context("failed to PEM-encode attested TLS certificate")?;
+    let key_pem = cert.serialize_private_key_pem();
+
+    Ok((cert_pem, key_pem))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_data_for_public_key_is_64_bytes() {
+        let report_data = report_data_for_public_key(b"some DER-encoded public key");
+        assert_eq!(report_data.len(), 64);
+    }
+
+    #[test]
+    fn report_data_for_public_key_is_deterministic_and_known() {
+        // SHA-512("") is a standard known-answer value; any correct SHA-512
+        // implementation must reproduce it.
+        let expected = hex::decode(
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+        )
+        .unwrap();
+        assert_eq!(report_data_for_public_key(b"").to_vec(), expected);
+    }
+
+    #[test]
+    fn report_data_changes_with_the_key() {
+        let a = report_data_for_public_key(b"key a");
+        let b = report_data_for_public_key(b"key b");
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,52 @@
+//! Generates a self-signed TLS certificate whose key pair is bound to a fresh SEV-SNP
+//! attestation report, turning the "copy a JSON report around" flow into something a
+//! relying party can verify over a live TLS connection.
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Parser;
+use sev::firmware::guest::Firmware;
+use snafu::{ResultExt, Whatever};
+
+use attestation_server::attested_tls::{generate_attested_cert, generate_attested_key_pair};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Path to write the PEM-encoded attested certificate
+    #[arg(long, default_value = "attested_cert.pem")]
+    out_cert: PathBuf,
+
+    /// Path to write the PEM-encoded private key
+    #[arg(long, default_value = "attested_key.pem")]
+    out_key: PathBuf,
+}
+
+#[snafu::report]
+fn main() -> Result<(), Whatever> {
+    let args = Args::parse();
+
+    let attested_key_pair = generate_attested_key_pair()?;
+
+    let mut fw = Firmware::open()
+        .whatever_context("failed to open sev firmware device. Is this a SEV-SNP guest?")?;
+    let report = fw
+        .get_report(None, Some(attested_key_pair.report_data), None)
+        .whatever_context("error getting report from firmware device")?;
+
+    let (cert_pem, key_pem) = generate_attested_cert(attested_key_pair, &report, None)?;
+
+    fs::write(&args.out_cert, cert_pem)
+        .whatever_context(format!("failed to write certificate to {:?}", args.out_cert))?;
+    fs::write(&args.out_key, key_pem)
+        .whatever_context(format!("failed to write private key to {:?}", args.out_key))?;
+
+    println!(
+        "Attested TLS certificate written to {:?} (key at {:?}). The attestation report is \
+         embedded in the certificate's custom extension; a relying party can extract and \
+         verify it with `verify_report` and check it hashes to this certificate's public key.",
+        args.out_cert, args.out_key
+    );
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! Generates an SEV-SNP ID block and ID-auth block for a `VMDescription`, so QEMU's
+//! `sev-guest` `id-block=`/`id-auth=` parameters can *enforce* the expected launch
+//! measurement rather than merely allow it to be checked afterward.
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Parser;
+use p384::pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding};
+use snafu::{ResultExt, Whatever};
+
+use attestation_server::calc_expected_ld::VMDescription;
+use attestation_server::id_block::{generate_id_blocks, generate_signing_key};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Path to the VMDescription TOML config to compute the launch digest from
+    #[arg(long)]
+    vm_config: PathBuf,
+
+    /// PEM-encoded PKCS#8 ECDSA P-384 ID key. A fresh key is generated if not given.
+    #[arg(long)]
+    id_key: Option<PathBuf>,
+
+    /// PEM-encoded PKCS#8 ECDSA P-384 author key. If omitted, the ID-auth block is
+    /// emitted with no author signature, and QEMU's `author-key-enabled` property
+    /// should be left at its default (disabled).
+    #[arg(long)]
+    author_key: Option<PathBuf>,
+
+    /// Where to write the newly generated ID key, if one was generated
+    #[arg(long, default_value = "id_key.pem")]
+    out_id_key: PathBuf,
+
+    /// Where to write the base64 ID block
+    #[arg(long, default_value = "id_block.base64")]
+    out_id_block: PathBuf,
+
+    /// Where to write the base64 ID-auth block
+    #[arg(long, default_value = "id_auth.base64")]
+    out_id_auth: PathBuf,
+
+    /// `version` field of the ID block
+    #[arg(long, default_value = "1")]
+    version: u32,
+
+    /// `guest_svn` field of the ID block
+    #[arg(long, default_value = "0")]
+    guest_svn: u32,
+}
+
+#[snafu::report]
+fn main() -> Result<(), Whatever> {
+    let args = Args::parse();
+
+    let vm_config_str = fs::read_to_string(&args.vm_config)
+        .whatever_context(format!("failed to read VM config at {:?}", args.vm_config))?;
+    let vm_description: VMDescription =
+        toml::from_str(&vm_config_str).whatever_context("failed to parse VM config as TOML")?;
+    let launch_digest = vm_description
+        .compute_expected_hash()
+        .whatever_context("failed to compute expected launch digest")?;
+
+    let id_key = match &args.id_key {
+        Some(path) => {
+            let pem = fs::read_to_string(path)
+                .whatever_context(format!("failed to read ID key at {:?}", path))?;
+            p384::ecdsa::SigningKey::from_pkcs8_pem(&pem)
+                .whatever_context("failed to parse ID key as PKCS#8 PEM")?
+        }
+        None => {
+            let key = generate_signing_key();
+            let pem = key
+                .to_pkcs8_pem(LineEnding::LF)
+                .whatever_context("failed to encode generated ID key as PKCS#8 PEM")?;
+            fs::write(&args.out_id_key, pem.as_bytes())
+                .whatever_context(format!("failed to write generated ID key to {:?}", args.out_id_key))?;
+            println!("Generated a new ID key at {:?}", args.out_id_key);
+            key
+        }
+    };
+
+    let author_key = args
+        .author_key
+        .as_ref()
+        .map(|path| {
+            let pem = fs::read_to_string(path)
+                .whatever_context(format!("failed to read author key at {:?}", path))?;
+            p384::ecdsa::SigningKey::from_pkcs8_pem(&pem)
+                .whatever_context("failed to parse author key as PKCS#8 PEM")
+        })
+        .transpose()?;
+
+    let (id_block, id_auth_block) = generate_id_blocks(
+        launch_digest,
+        vm_description.family_id,
+        vm_description.image_id,
+        args.version,
+        args.guest_svn,
+        vm_description.guest_policy.0,
+        &id_key,
+        author_key.as_ref(),
+    )?;
+
+    fs::write(&args.out_id_block, id_block.to_base64())
+        .whatever_context(format!("failed to write ID block to {:?}", args.out_id_block))?;
+    fs::write(&args.out_id_auth, id_auth_block.to_base64())
+        .whatever_context(format!("failed to write ID-auth block to {:?}", args.out_id_auth))?;
+
+    println!("ID block written to {:?}", args.out_id_block);
+    println!("ID-auth block written to {:?}", args.out_id_auth);
+    println!(
+        "Pass them to QEMU as: -object sev-snp-guest,...,id-block=$(cat {:?}),id-auth=$(cat {:?}),author-key-enabled={}",
+        args.out_id_block, args.out_id_auth, id_auth_block.author_key_enabled
+    );
+
+    Ok(())
+}
@@ -1,5 +1,4 @@
 use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Seek, SeekFrom};
 use std::path::PathBuf;
 
 use clap::Parser;
@@ -9,36 +8,10 @@ use base64::{engine::general_purpose, Engine};
 use anyhow::Result;
 use serde_json::json;
 
-
-// SEV status MSR is defined in /AMDSEV/linux/guest/arch/x86/include/asm/msr-index.h
-const MSR_AMD64_SEV: u32 = 0xC0010131;
-const MSR_SIZE: usize = 8; // MSRs are 64-bit (8 bytes)
-
-// Reads a 64-bit MSR value for a specific CPU core.
-//
-// # Arguments
-// * `cpu_id` - The ID of the CPU core (e.g., 0 for /dev/cpu/0/msr).
-// * `msr_index` - The 32-bit index of the MSR to read.
-//
-// # Returns
-// A `Result` containing the 64-bit MSR value on success, or an `io::Error` on failure.
-fn read_msr_value(cpu_id: u32, msr_index: u32) -> io::Result<u64> {
-    let path = PathBuf::from(format!("/dev/cpu/{}/msr", cpu_id));
-
-    let mut file = File::open(&path)?;
-
-    // Seek to the MSR index (offset within the msr device file)
-    file.seek(SeekFrom::Start(msr_index as u64))?;
-
-    // Read 8 bytes (64 bits)
-    let mut buffer = [0u8; MSR_SIZE];
-    file.read_exact(&mut buffer)?;
-
-    // Convert the 8 bytes to a u64
-    let msr_value = u64::from_le_bytes(buffer);
-
-    Ok(msr_value)
-}
+use attestation_server::calc_expected_ld::{
+    check_guest_features_match, format_guest_features, guest_features_from_sev_status,
+    read_msr_value, VMDescription, MSR_AMD64_SEV,
+};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -62,6 +35,12 @@ struct Args {
     /// Path to binary output file (can be used with snpguest tool)
     #[arg(long, default_value = "sev_feature.json")]
     out_sev: String,
+
+    /// Optional path to the VMDescription TOML config. If set, the guest_features
+    /// detected from MSR_AMD64_SEV are checked against the config's guest_features and
+    /// the program refuses to emit a report on mismatch.
+    #[arg(long)]
+    vm_config: Option<PathBuf>,
 }
 #[snafu::report]
 fn main() -> Result<(), Whatever> {
@@ -106,6 +85,21 @@ fn main() -> Result<(), Whatever> {
                 .whatever_context("failed to create SEV feature file report")?;
              serde_json::to_writer(&mut file, &sev_feature_json).whatever_context("failed to write to file")?;
              println!("SEV feature saved in {}", &args.out_sev);
+
+            let actual_features = guest_features_from_sev_status(sev_status_value);
+            println!("  Detected guest_features: {}", format_guest_features(&actual_features));
+
+            if let Some(vm_config_path) = &args.vm_config {
+                let vm_config_str = std::fs::read_to_string(vm_config_path)
+                    .whatever_context(format!("failed to read VM config at {:?}", vm_config_path))?;
+                let vm_description: VMDescription = toml::from_str(&vm_config_str)
+                    .whatever_context("failed to parse VM config as TOML")?;
+                check_guest_features_match(&vm_description.guest_features, &actual_features)?;
+                println!(
+                    "guest_features match the committed VMDescription ({})",
+                    format_guest_features(&actual_features)
+                );
+            }
         },
         Err(e) => {
             eprintln!("\nError reading MSR 0x{:x} on CPU {}: {}", MSR_AMD64_SEV, args.cpu_id, e);
@@ -114,6 +108,19 @@ fn main() -> Result<(), Whatever> {
             eprintln!("  2. You need 'sudo' to run this program.");
             eprintln!("  3. The MSR index 0x{:x} might not be valid or accessible on your specific CPU.", MSR_AMD64_SEV);
             eprintln!("  4. The cpu_id ({}) may not exist.", args.cpu_id);
+
+            // A host that makes this read fail (module not loaded, permission denied) must
+            // not be able to bypass the guest_features gate above by doing so -- when
+            // vm_config is set, a failed MSR read is a hard error, not a warning.
+            if args.vm_config.is_some() {
+                whatever!(
+                    "cannot verify guest_features against the committed VMDescription: \
+                     failed to read MSR 0x{:x} on CPU {}: {}",
+                    MSR_AMD64_SEV,
+                    args.cpu_id,
+                    e
+                );
+            }
         },
     }
 
@@ -0,0 +1,83 @@
+//! Verifies an attestation report produced by `get_report` against the AMD KDS
+//! certificate chain and the policy committed to in a `VMDescription`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use base64::{engine::general_purpose, Engine};
+use clap::Parser;
+use sev::certs::snp::Certificate;
+use sev::firmware::guest::AttestationReport;
+use snafu::{whatever, ResultExt, Whatever};
+
+use attestation_server::calc_expected_ld::VMDescription;
+use attestation_server::snp_validate_report::verify_report;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Path to the attestation report JSON produced by `get_report`
+    #[arg(long, default_value = "attestation_report.json")]
+    report: PathBuf,
+
+    /// Path to the VMDescription TOML config the report is checked against
+    #[arg(long)]
+    vm_config: PathBuf,
+
+    /// PEM-encoded AMD root (ARK) certificate for the guest's product line
+    #[arg(long)]
+    ark: PathBuf,
+
+    /// PEM-encoded AMD signing-key (ASK) certificate for the guest's product line
+    #[arg(long)]
+    ask: PathBuf,
+
+    /// Base64-encoded 64-byte nonce expected in the report's `report_data`. Defaults to
+    /// all-zero, i.e. whatever `report_data` the guest binary used by default.
+    #[arg(long, default_value = "")]
+    expected_report_data: String,
+}
+
+#[snafu::report]
+fn main() -> Result<(), Whatever> {
+    let args = Args::parse();
+
+    let report_json =
+        fs::read_to_string(&args.report).whatever_context(format!("failed to read report at {:?}", args.report))?;
+    let report: AttestationReport =
+        serde_json::from_str(&report_json).whatever_context("failed to parse report JSON")?;
+
+    let vm_config_str = fs::read_to_string(&args.vm_config)
+        .whatever_context(format!("failed to read VM config at {:?}", args.vm_config))?;
+    let vm_description: VMDescription =
+        toml::from_str(&vm_config_str).whatever_context("failed to parse VM config as TOML")?;
+
+    let ark_pem = fs::read_to_string(&args.ark).whatever_context(format!("failed to read ARK at {:?}", args.ark))?;
+    let ask_pem = fs::read_to_string(&args.ask).whatever_context(format!("failed to read ASK at {:?}", args.ask))?;
+    let ark = Certificate::from_pem(ark_pem.as_bytes()).whatever_context("failed to parse ARK as PEM")?;
+    let ask = Certificate::from_pem(ask_pem.as_bytes()).whatever_context("failed to parse ASK as PEM")?;
+
+    let expected_report_data_raw = general_purpose::STANDARD_NO_PAD
+        .decode(&args.expected_report_data)
+        .whatever_context("failed to decode expected_report_data as base64")?;
+    if expected_report_data_raw.len() > 64 {
+        whatever!(
+            "expected_report_data length should be <= 64 bytes, but got {} bytes!",
+            expected_report_data_raw.len()
+        );
+    }
+    let mut expected_report_data = [0u8; 64];
+    expected_report_data[..expected_report_data_raw.len()].copy_from_slice(&expected_report_data_raw);
+
+    let result = verify_report(&report, ark, ask, &vm_description, &expected_report_data)?;
+
+    if result.passed() {
+        println!("Report verification PASSED: signature, certificate chain and policy all check out.");
+        Ok(())
+    } else {
+        println!("Report verification FAILED:");
+        for failure in &result.failures {
+            println!("  - {:?}", failure);
+        }
+        whatever!("report failed {} verification check(s)", result.failures.len());
+    }
+}
@@ -1,3 +1,7 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 use sev::firmware::guest::{GuestPolicy, PlatformInfo};
 use sev::firmware::host::TcbVersion;
@@ -14,15 +18,72 @@ use hex_buffer_serde::{Hex as _, HexForm};
 ///Length fo the FamilyID and the ImageID data types in bytes
 pub const IDBLOCK_ID_BYTES :usize = 16;
 
-#[derive(Serialize, Deserialize, Default)]
+// SEV status MSR is defined in /AMDSEV/linux/guest/arch/x86/include/asm/msr-index.h
+// Used by the `get_report` binary, which reads this same MSR.
+pub const MSR_AMD64_SEV: u32 = 0xC0010131;
+const MSR_SIZE: usize = 8; // MSRs are 64-bit (8 bytes)
+
+/// Number of `SEV_FEATURES` bits documented in Table B-4 of the APM (AMD doc #24593),
+/// i.e. up to and including `SmtProtection` (bit 15). Bits above this are `SEV_STATUS` bits that
+/// aren't part of the VMSA `SEV_FEATURES` field and don't affect the launch measurement.
+const GUEST_FEATURES_BITS: u32 = 16;
+const GUEST_FEATURES_MASK: u64 = (1u64 << GUEST_FEATURES_BITS) - 1;
+
+/// Masks `features` down to the documented `SEV_FEATURES` bits, so two `GuestFeatures`
+/// values can be compared for measurement-equivalence without tripping over unrelated
+/// `SEV_STATUS` bits outside that range.
+fn mask_guest_features(features: GuestFeatures) -> GuestFeatures {
+    GuestFeatures(features.0 & GUEST_FEATURES_MASK)
+}
+
+/// Reads a 64-bit MSR value for a specific CPU core, e.g. `MSR_AMD64_SEV`.
+///
+/// # Arguments
+/// * `cpu_id` - The ID of the CPU core (e.g., 0 for /dev/cpu/0/msr).
+/// * `msr_index` - The 32-bit index of the MSR to read.
+pub fn read_msr_value(cpu_id: u32, msr_index: u32) -> std::io::Result<u64> {
+    let path = PathBuf::from(format!("/dev/cpu/{}/msr", cpu_id));
+
+    let mut file = File::open(&path)?;
+    file.seek(SeekFrom::Start(msr_index as u64))?;
+
+    let mut buffer = [0u8; MSR_SIZE];
+    file.read_exact(&mut buffer)?;
+
+    Ok(u64::from_le_bytes(buffer))
+}
+
+/// Derives the VMSA `SEV_FEATURES` bitfield from a guest's `SEV_STATUS` MSR value and
+/// wraps it in a `GuestFeatures`. `SEV_FEATURES` is exactly `SEV_STATUS >> 2`, masked to
+/// the bits documented in Table B-4. See
+/// `AMDSEV/ovmf/UefiCpuPkg/Library/MpInitLib/X64/AmdSev.c`.
+pub fn guest_features_from_sev_status(sev_status: u64) -> GuestFeatures {
+    mask_guest_features(GuestFeatures(sev_status >> 2))
+}
+
+fn default_vcpu_type() -> CpuType {
+    CpuType::EpycV4
+}
+
+fn default_vmm_type() -> VMMType {
+    VMMType::QEMU
+}
+
+#[derive(Serialize, Deserialize)]
 ///User facing config struct to specify a VM.
 ///Used to compute the epxected launch measurment
 pub struct VMDescription {
     pub host_cpu_family: ProductName,
     pub vcpu_count: u32,
+    /// vCPU model assumed when computing the launch digest. Different EPYC generations
+    /// expose different VMSA save-state layouts, which changes the measurement. Defaults
+    /// to `EpycV4` to preserve prior behavior.
+    #[serde(default = "default_vcpu_type")]
+    pub vcpu_type: CpuType,
     pub ovmf_file: String,
     /// Security relevant SEV configuration/kernel features. Defined in the VMSA of the VM. Thus they affect the computation of the expected launch measurement. See `SEV_FEATURES` in Table B-4 in https://www.amd.com/content/dam/amd/en/documents/processor-tech-docs/programmer-references/24593.pdf
-    ///TODO: implement nice way to detect which features are used on a given system
+    /// Can be auto-detected on a running guest by reading `MSR_AMD64_SEV` (see
+    /// `read_msr_value`) and passing the result through `guest_features_from_sev_status`.
     pub guest_features: GuestFeatures,
     pub kernel_file: String,
     pub initrd_file: String,
@@ -38,6 +99,32 @@ pub struct VMDescription {
     pub family_id: [u8; IDBLOCK_ID_BYTES],
     #[serde(with = "HexForm")]
     pub image_id: [u8; IDBLOCK_ID_BYTES],
+    /// VMM stack the guest is launched under. QEMU, EC2 and KRUN lay out the initial VMSA
+    /// and OVMF metadata tables differently, producing distinct launch digests. Defaults
+    /// to `QEMU` to preserve prior behavior.
+    #[serde(default = "default_vmm_type")]
+    pub vmm_type: VMMType,
+}
+
+impl Default for VMDescription {
+    fn default() -> Self {
+        Self {
+            host_cpu_family: Default::default(),
+            vcpu_count: Default::default(),
+            vcpu_type: default_vcpu_type(),
+            ovmf_file: Default::default(),
+            guest_features: Default::default(),
+            kernel_file: Default::default(),
+            initrd_file: Default::default(),
+            kernel_cmdline: Default::default(),
+            platform_info: Default::default(),
+            min_commited_tcb: Default::default(),
+            guest_policy: Default::default(),
+            family_id: Default::default(),
+            image_id: Default::default(),
+            vmm_type: default_vmm_type(),
+        }
+    }
 }
 pub fn format_guest_features(features: &GuestFeatures) -> String {
     let mut enabled_features = Vec::new();
@@ -93,6 +180,36 @@ pub fn format_guest_features(features: &GuestFeatures) -> String {
     }
 }
 
+/// Compares the `guest_features` committed to a `VMDescription` against the features
+/// actually enabled on a running guest (e.g. from `guest_features_from_sev_status`), and
+/// refuses to proceed if they disagree. Mirrors the feature-compatibility check the
+/// SNP EFI stub does before trusting the hypervisor: if it enabled features the guest
+/// doesn't implement (or dropped ones the config expects), the VMSA and thus the launch
+/// measurement silently changed and the report can no longer be trusted.
+///
+/// Both sides are masked to the documented `SEV_FEATURES` bits before comparing, since
+/// this is a measurement-equivalence check and bits outside that range are `SEV_STATUS`
+/// bits that aren't part of the VMSA and don't affect the launch digest.
+pub fn check_guest_features_match(
+    expected: &GuestFeatures,
+    actual: &GuestFeatures,
+) -> Result<(), Whatever> {
+    let expected = mask_guest_features(*expected);
+    let actual = mask_guest_features(*actual);
+    if expected.0 == actual.0 {
+        return Ok(());
+    }
+
+    let expected_not_actual = GuestFeatures(expected.0 & !actual.0);
+    let actual_not_expected = GuestFeatures(actual.0 & !expected.0);
+    whatever!(
+        "guest_features mismatch between VMDescription and the running guest: \
+         expected but not enabled: [{}]; enabled but not expected: [{}]",
+        format_guest_features(&expected_not_actual),
+        format_guest_features(&actual_not_expected)
+    );
+}
+
 fn display_snp_measurement_args(snp_measure_args: &SnpMeasurementArgs<'_>) {
     println!("Computing expected launch digest based on:");
     println!("  vcpus:          {:?}", snp_measure_args.vcpus);
@@ -115,7 +232,7 @@ impl VMDescription {
     pub fn compute_expected_hash(&self) -> Result<[u8; 384 / 8], Whatever> {
         let snp_measure_args = SnpMeasurementArgs {
             vcpus: self.vcpu_count,
-            vcpu_type: CpuType::EpycV4,
+            vcpu_type: self.vcpu_type,
             ovmf_file: self.ovmf_file.clone().into(),
             guest_features: self.guest_features,
             kernel_file: Some(self.kernel_file.clone().into()),
@@ -127,7 +244,7 @@ impl VMDescription {
             },
             //if none, we calc ovmf hash based on ovmf file
             ovmf_hash_str: None,
-            vmm_type: Some(VMMType::QEMU),
+            vmm_type: Some(self.vmm_type),
         };
         display_snp_measurement_args(&snp_measure_args);
 
@@ -146,7 +263,7 @@ impl VMDescription {
 mod test {
     use std::fs;
 
-    use super::VMDescription;
+    use super::{check_guest_features_match, guest_features_from_sev_status, VMDescription};
 
     #[test]
     fn parse_toml() {
@@ -157,4 +274,19 @@ mod test {
         let _conf: VMDescription =
             toml::from_str(&fs::read_to_string("./examples/vm-config.toml").unwrap()).unwrap();
     }
+
+    #[test]
+    fn guest_features_from_sev_status_masks_reserved_status_bits() {
+        // bit 2 of SEV_STATUS is SEV_FEATURES bit 0; bit 40 of SEV_STATUS lands well above
+        // the 16 documented SEV_FEATURES bits and must be masked away.
+        let sev_status = (1u64 << 2) | (1u64 << 40);
+        assert_eq!(guest_features_from_sev_status(sev_status).0, 1);
+    }
+
+    #[test]
+    fn check_guest_features_match_ignores_reserved_status_bits() {
+        let expected = guest_features_from_sev_status(1u64 << 2);
+        let actual = guest_features_from_sev_status((1u64 << 2) | (1u64 << 40));
+        assert!(check_guest_features_match(&expected, &actual).is_ok());
+    }
 }
@@ -0,0 +1,275 @@
+//! Verification of SEV-SNP attestation reports: the AMD KDS certificate chain, the
+//! report's own ECDSA signature, and the policy an operator committed to in a
+//! `VMDescription`.
+//!
+//! The guest-side binaries in this crate only ever produce a report; nothing in-crate
+//! checked it cryptographically before, forcing operators out to a separate
+//! `verify_report`-shaped tool. This module closes that gap end to end.
+
+use serde::{Deserialize, Serialize};
+use sev::certs::snp::{ca::Chain as CaChain, Certificate, Chain, Verifiable};
+use sev::firmware::guest::AttestationReport;
+use sev::firmware::host::TcbVersion;
+use snafu::{whatever, ResultExt, Whatever};
+
+use crate::calc_expected_ld::VMDescription;
+
+/// AMD EPYC product line a report was generated on. Selects the KDS URL path component
+/// used to fetch the VCEK. No ARK/ASK certificates are built in; `verify_cert_chain`
+/// takes them from the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ProductName {
+    #[default]
+    Milan,
+    Genoa,
+    Turin,
+}
+
+impl ProductName {
+    fn kds_path_component(&self) -> &'static str {
+        match self {
+            ProductName::Milan => "Milan",
+            ProductName::Genoa => "Genoa",
+            ProductName::Turin => "Turin",
+        }
+    }
+}
+
+/// A single predicate checked while verifying a report. Carried in `VerificationReport`
+/// so callers can tell exactly what failed instead of getting a single opaque error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationFailure {
+    /// The ARK -> ASK -> VCEK certificate chain does not verify.
+    CertChainInvalid(String),
+    /// The report's ECDSA signature does not verify under the VCEK public key.
+    ReportSignatureInvalid,
+    /// The measured launch digest does not match `VMDescription::compute_expected_hash`.
+    LaunchDigestMismatch,
+    /// The reported TCB is below the committed minimum, i.e. a rollback.
+    TcbRollback {
+        min_commited: TcbVersion,
+        reported: TcbVersion,
+    },
+    /// `guest_policy` in the report does not match the committed `VMDescription`.
+    GuestPolicyMismatch,
+    /// `platform_info` in the report does not match the committed `VMDescription`.
+    PlatformInfoMismatch,
+    /// `report_data` does not match the nonce the caller expected.
+    ReportDataMismatch,
+}
+
+/// The outcome of verifying a report against a `VMDescription`: which of the above
+/// predicates, if any, failed.
+#[derive(Debug, Clone, Default)]
+pub struct VerificationReport {
+    pub failures: Vec<VerificationFailure>,
+}
+
+impl VerificationReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Fetches the VCEK leaf certificate for `chip_id`/`reported_tcb` from the AMD KDS.
+pub fn fetch_vcek(
+    product: ProductName,
+    chip_id: &[u8],
+    reported_tcb: &TcbVersion,
+) -> Result<Certificate, Whatever> {
+    let url = format!(
+        "https://kdsintf.amd.com/vcek/v1/{product}/{chip_id}?blSPL={bl}&teeSPL={tee}&snpSPL={snp}&ucodeSPL={ucode}",
+        product = product.kds_path_component(),
+        chip_id = hex::encode(chip_id),
+        bl = reported_tcb.bootloader,
+        tee = reported_tcb.tee,
+        snp = reported_tcb.snp,
+        ucode = reported_tcb.microcode,
+    );
+
+    let der = reqwest::blocking::get(&url)
+        .whatever_context("failed to reach AMD KDS")?
+        .error_for_status()
+        .whatever_context("AMD KDS returned an error status")?
+        .bytes()
+        .whatever_context("failed to read VCEK response body")?;
+
+    Certificate::from_der(&der).whatever_context("failed to parse VCEK as a DER certificate")
+}
+
+/// Verifies the ARK -> ASK -> VCEK chain. `ark`/`ask` are the AMD root and signing-key
+/// certificates for the guest's product line (e.g. downloaded once from
+/// `https://kdsintf.amd.com/vcek/v1/{product}/cert_chain`, since unlike the VCEK they
+/// don't depend on `chip_id`/TCB and don't need refetching per report).
+pub fn verify_cert_chain(ark: Certificate, ask: Certificate, vcek: Certificate) -> Result<(), Whatever> {
+    let chain = Chain {
+        ca: CaChain { ark, ask },
+        vek: vcek,
+    };
+
+    chain
+        .verify()
+        .whatever_context("ARK -> ASK -> VCEK certificate chain failed to verify")
+}
+
+/// Verifies the report's own ECDSA P-384 signature (over the first 0x2A0 bytes of the
+/// report) under the VCEK's public key.
+pub fn verify_report_signature(report: &AttestationReport, vcek: &Certificate) -> Result<(), Whatever> {
+    (report, vcek)
+        .verify()
+        .whatever_context("attestation report signature failed to verify under the VCEK")
+}
+
+/// The four independent SVN components of a `TcbVersion`. SNP tracks each as its own
+/// non-decreasing counter, so a rollback check must compare them independently rather
+/// than relying on `TcbVersion`'s (if derived) lexicographic `PartialOrd`, which would
+/// only catch a rollback in whichever field is compared first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TcbComponents {
+    bootloader: u8,
+    tee: u8,
+    snp: u8,
+    microcode: u8,
+}
+
+impl From<&TcbVersion> for TcbComponents {
+    fn from(tcb: &TcbVersion) -> Self {
+        Self {
+            bootloader: tcb.bootloader,
+            tee: tcb.tee,
+            snp: tcb.snp,
+            microcode: tcb.microcode,
+        }
+    }
+}
+
+impl TcbComponents {
+    /// True if `self` (the reported TCB) has rolled back any component below
+    /// `min_commited`.
+    fn has_rollback_from(&self, min_commited: &TcbComponents) -> bool {
+        self.bootloader < min_commited.bootloader
+            || self.tee < min_commited.tee
+            || self.snp < min_commited.snp
+            || self.microcode < min_commited.microcode
+    }
+}
+
+/// Checks `report` against the policy committed to in `vm_description`: the measured
+/// digest, minimum committed TCB, guest policy, platform info, and an expected nonce in
+/// `report_data`. Does not touch the certificate chain or report signature; call
+/// `verify_cert_chain`/`verify_report_signature` first to establish that the report
+/// itself can be trusted.
+pub fn check_report_against_policy(
+    report: &AttestationReport,
+    vm_description: &VMDescription,
+    expected_report_data: &[u8; 64],
+) -> Result<VerificationReport, Whatever> {
+    let mut failures = Vec::new();
+
+    let expected_digest = vm_description
+        .compute_expected_hash()
+        .whatever_context("failed to compute expected launch digest")?;
+    if report.measurement.as_slice() != expected_digest.as_slice() {
+        failures.push(VerificationFailure::LaunchDigestMismatch);
+    }
+
+    let reported_tcb = TcbComponents::from(&report.reported_tcb);
+    let min_commited_tcb = TcbComponents::from(&vm_description.min_commited_tcb);
+    if reported_tcb.has_rollback_from(&min_commited_tcb) {
+        failures.push(VerificationFailure::TcbRollback {
+            min_commited: vm_description.min_commited_tcb,
+            reported: report.reported_tcb,
+        });
+    }
+
+    if report.policy != vm_description.guest_policy {
+        failures.push(VerificationFailure::GuestPolicyMismatch);
+    }
+
+    if report.plat_info != vm_description.platform_info {
+        failures.push(VerificationFailure::PlatformInfoMismatch);
+    }
+
+    if &report.report_data != expected_report_data {
+        failures.push(VerificationFailure::ReportDataMismatch);
+    }
+
+    Ok(VerificationReport { failures })
+}
+
+/// Runs the full pipeline: fetches the VCEK from the AMD KDS, verifies the ARK -> ASK ->
+/// VCEK chain, verifies the report signature, then checks the report against
+/// `vm_description`. Returns a structured pass/fail naming each failing predicate. A
+/// failing cert chain or report signature means the report cannot be trusted at all, so
+/// it is returned as the only `VerificationFailure` and the policy checks are skipped,
+/// since none of them would be meaningful. Failing to fetch the VCEK is a `Whatever` error.
+pub fn verify_report(
+    report: &AttestationReport,
+    ark: Certificate,
+    ask: Certificate,
+    vm_description: &VMDescription,
+    expected_report_data: &[u8; 64],
+) -> Result<VerificationReport, Whatever> {
+    let vcek = fetch_vcek(
+        vm_description.host_cpu_family,
+        &report.chip_id,
+        &report.reported_tcb,
+    )?;
+
+    if let Err(e) = verify_cert_chain(ark, ask, vcek.clone()) {
+        return Ok(VerificationReport {
+            failures: vec![VerificationFailure::CertChainInvalid(e.to_string())],
+        });
+    }
+    if verify_report_signature(report, &vcek).is_err() {
+        return Ok(VerificationReport {
+            failures: vec![VerificationFailure::ReportSignatureInvalid],
+        });
+    }
+
+    check_report_against_policy(report, vm_description, expected_report_data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equal_tcb_is_not_a_rollback() {
+        let tcb = TcbComponents { bootloader: 4, tee: 9, snp: 2, microcode: 100 };
+        assert!(!tcb.has_rollback_from(&tcb));
+    }
+
+    #[test]
+    fn rollback_in_any_single_component_is_caught() {
+        let min_commited = TcbComponents { bootloader: 4, tee: 9, snp: 2, microcode: 100 };
+
+        // A naive lexicographic `<` over the whole struct would treat {bl:5, tee:0} as
+        // "greater" than {bl:4, tee:9} purely because bootloader (the first field)
+        // increased, even though tee rolled back from 9 to 0.
+        let rolled_back_tee = TcbComponents { bootloader: 5, tee: 0, ..min_commited };
+        assert!(rolled_back_tee.has_rollback_from(&min_commited));
+
+        let rolled_back_snp = TcbComponents { snp: 1, ..min_commited };
+        assert!(rolled_back_snp.has_rollback_from(&min_commited));
+
+        let rolled_back_microcode = TcbComponents { microcode: 99, ..min_commited };
+        assert!(rolled_back_microcode.has_rollback_from(&min_commited));
+    }
+
+    #[test]
+    fn tcb_advancing_in_every_component_is_not_a_rollback() {
+        let min_commited = TcbComponents { bootloader: 4, tee: 9, snp: 2, microcode: 100 };
+        let advanced = TcbComponents { bootloader: 5, tee: 10, snp: 3, microcode: 101 };
+        assert!(!advanced.has_rollback_from(&min_commited));
+    }
+
+    #[test]
+    fn verification_report_passes_only_with_no_failures() {
+        assert!(VerificationReport::default().passed());
+        assert!(!VerificationReport {
+            failures: vec![VerificationFailure::ReportDataMismatch]
+        }
+        .passed());
+    }
+}